@@ -0,0 +1,249 @@
+use crate::{
+    Server,
+    auth::{Auth, Operation},
+    persist::Mutation,
+    route::{Route, normalize},
+};
+use axum::{
+    Json, Router,
+    extract::{
+        ConnectInfo, Path, Query, State,
+        rejection::{JsonRejection, PathRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, put},
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::BTreeMap, fmt::Display, net::SocketAddr, sync::Arc};
+use tracing::info;
+
+pub fn error(status: StatusCode, message: impl Display) -> Response {
+    (status, Json(json!({ "error": message.to_string() }))).into_response()
+}
+
+#[derive(Deserialize)]
+struct Register {
+    redirect_url: Option<String>,
+}
+
+impl Server {
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/register/{hostname}", get(Server::register))
+            .route("/routes", get(Server::list_routes))
+            .route(
+                "/routes/{hostname}/{list}/{player}",
+                put(Server::add_player).delete(Server::remove_player),
+            )
+            .route("/bans", get(Server::list_bans))
+            .route(
+                "/bans/{*cidr}",
+                put(Server::put_ban).delete(Server::delete_ban),
+            )
+            .route("/metrics", get(Server::metrics))
+            .route("/sessions", get(Server::list_sessions))
+            .route("/sessions/{id}", delete(Server::kick_session))
+            .route(
+                "/routes/{hostname}",
+                get(Server::get_route)
+                    .put(Server::put_route)
+                    .delete(Server::delete_route),
+            )
+            .with_state(self)
+    }
+
+    async fn register(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Query(Register { redirect_url }): Query<Register>,
+        Path(hostname): Path<String>,
+        ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ) -> Response {
+        let hostname = normalize(&hostname);
+        if let Some(response) = auth.deny(Operation::Register, &hostname) {
+            return response;
+        }
+        let origin = SocketAddr::new(addr.ip(), 25565);
+        info!("registered route {hostname} to {origin}");
+        let route = Arc::new(Route::new(origin));
+        server.routes.load().insert(hostname.clone(), route.clone());
+        server.persist(Mutation::Put { hostname, route }).await;
+        if let Some(redirect_url) = redirect_url {
+            Redirect::to(redirect_url.as_str()).into_response()
+        } else {
+            ().into_response()
+        }
+    }
+
+    async fn list_routes(State(server): State<Arc<Server>>, auth: Auth) -> Response {
+        let routes = server
+            .routes
+            .load()
+            .iter()
+            .filter(|route| auth.allows(Operation::Read, route.key()))
+            .map(|route| (route.key().clone(), route.value().clone()))
+            .collect::<BTreeMap<_, _>>();
+        Json(routes).into_response()
+    }
+
+    async fn get_route(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Path(hostname): Path<String>,
+    ) -> Response {
+        let hostname = normalize(&hostname);
+        if let Some(response) = auth.deny(Operation::Read, &hostname) {
+            return response;
+        }
+        match server.routes.load().get(&hostname) {
+            Some(route) => Json(route.clone()).into_response(),
+            None => error(StatusCode::NOT_FOUND, format!("no route for {hostname}")),
+        }
+    }
+
+    async fn put_route(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        hostname: Result<Path<String>, PathRejection>,
+        route: Result<Json<Route>, JsonRejection>,
+    ) -> Response {
+        let hostname = match hostname {
+            Ok(Path(hostname)) => normalize(&hostname),
+            Err(rejection) => return error(rejection.status(), rejection.body_text()),
+        };
+        if let Some(response) = auth.deny(Operation::Register, &hostname) {
+            return response;
+        }
+        let route = match route {
+            Ok(Json(route)) => route,
+            Err(rejection) => return error(rejection.status(), rejection.body_text()),
+        };
+        if let Err(message) = route.validate() {
+            return error(StatusCode::BAD_REQUEST, message);
+        }
+        info!("registered route {hostname} to {:?}", route.origins);
+        let route = Arc::new(route);
+        let status = match server.routes.load().insert(hostname.clone(), route.clone()) {
+            Some(_) => StatusCode::OK,
+            None => StatusCode::CREATED,
+        };
+        server
+            .persist(Mutation::Put {
+                hostname,
+                route: route.clone(),
+            })
+            .await;
+        (status, Json(route)).into_response()
+    }
+
+    async fn delete_route(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Path(hostname): Path<String>,
+    ) -> Response {
+        let hostname = normalize(&hostname);
+        if let Some(response) = auth.deny(Operation::Delete, &hostname) {
+            return response;
+        }
+        let route = server.routes.load().remove(&hostname);
+        match route {
+            Some(_) => {
+                info!("deleted route {hostname}");
+                server.persist(Mutation::Delete { hostname }).await;
+                StatusCode::NO_CONTENT.into_response()
+            }
+            None => error(StatusCode::NOT_FOUND, format!("no route for {hostname}")),
+        }
+    }
+
+    async fn update_players(
+        server: Arc<Server>,
+        auth: Auth,
+        hostname: String,
+        list: String,
+        player: String,
+        add: bool,
+    ) -> Response {
+        let hostname = normalize(&hostname);
+        if let Some(response) = auth.deny(Operation::Register, &hostname) {
+            return response;
+        }
+        let route = server.routes.load().get_mut(&hostname).map(|mut route| {
+            let players = Arc::make_mut(&mut route).players(&list)?;
+            players.retain(|entry| !entry.eq_ignore_ascii_case(&player));
+            if add {
+                players.push(player.clone());
+            }
+            Some(route.clone())
+        });
+        match route {
+            Some(Some(route)) => {
+                info!("updated {list} of {hostname}");
+                server
+                    .persist(Mutation::Put {
+                        hostname,
+                        route: route.clone(),
+                    })
+                    .await;
+                Json(route).into_response()
+            }
+            Some(None) => error(StatusCode::NOT_FOUND, format!("no player list {list}")),
+            None => error(StatusCode::NOT_FOUND, format!("no route for {hostname}")),
+        }
+    }
+
+    async fn add_player(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Path((hostname, list, player)): Path<(String, String, String)>,
+    ) -> Response {
+        Self::update_players(server, auth, hostname, list, player, true).await
+    }
+
+    async fn remove_player(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Path((hostname, list, player)): Path<(String, String, String)>,
+    ) -> Response {
+        Self::update_players(server, auth, hostname, list, player, false).await
+    }
+
+    async fn list_sessions(State(server): State<Arc<Server>>, auth: Auth) -> Response {
+        let mut sessions = server
+            .sessions
+            .sessions
+            .iter()
+            .filter(|session| auth.allows(Operation::Read, &session.hostname))
+            .map(|session| session.value().clone())
+            .collect::<Vec<_>>();
+        sessions.sort_by_key(|session| session.id);
+        Json(sessions).into_response()
+    }
+
+    async fn kick_session(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        id: Result<Path<u64>, PathRejection>,
+    ) -> Response {
+        let id = match id {
+            Ok(Path(id)) => id,
+            Err(rejection) => return error(rejection.status(), rejection.body_text()),
+        };
+        let session = server
+            .sessions
+            .sessions
+            .get(&id)
+            .map(|session| session.clone());
+        let Some(session) = session else {
+            return error(StatusCode::NOT_FOUND, format!("no session {id}"));
+        };
+        if let Some(response) = auth.deny(Operation::Delete, &session.hostname) {
+            return response;
+        }
+        info!("kicking session {id} from {}", session.addr);
+        session.kick.notify_one();
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{Server, api::error, route::matches};
+use axum::{
+    extract::FromRequestParts,
+    http::{StatusCode, header::AUTHORIZATION, request::Parts},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Register,
+    Delete,
+    Read,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TokenConfig {
+    pub hostnames: Vec<String>,
+    pub operations: Vec<Operation>,
+}
+
+impl TokenConfig {
+    fn allows(&self, operation: Operation, hostname: &str) -> bool {
+        self.operations.contains(&operation)
+            && self
+                .hostnames
+                .iter()
+                .any(|pattern| matches(pattern, hostname))
+    }
+}
+
+pub struct Auth(Option<TokenConfig>);
+
+impl Auth {
+    pub fn allows(&self, operation: Operation, hostname: &str) -> bool {
+        self.0
+            .as_ref()
+            .is_none_or(|token| token.allows(operation, hostname))
+    }
+
+    pub fn deny(&self, operation: Operation, hostname: &str) -> Option<Response> {
+        if self.allows(operation, hostname) {
+            None
+        } else {
+            Some(error(
+                StatusCode::FORBIDDEN,
+                format!("token is not allowed to access {hostname}"),
+            ))
+        }
+    }
+}
+
+impl FromRequestParts<Arc<Server>> for Auth {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        server: &Arc<Server>,
+    ) -> Result<Self, Self::Rejection> {
+        if server.tokens.is_empty() {
+            return Ok(Auth(None));
+        }
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .and_then(|token| server.tokens.get(token.trim()));
+        match token {
+            Some(token) => Ok(Auth(Some(token.clone()))),
+            None => Err(error(
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid bearer token",
+            )),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    Server,
+    metrics::{RouteMetrics, inc},
+    protocol::request_status,
+    route::Route,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    io,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{net::TcpStream, time};
+use tracing::{info, warn};
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    #[default]
+    FirstHealthy,
+    RoundRobin,
+    LeastConnections,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthCheck {
+    Tcp,
+    Status,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    pub kind: HealthCheck,
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            kind: HealthCheck::Tcp,
+            interval_secs: 10,
+            timeout_secs: 3,
+        }
+    }
+}
+
+pub struct Backend {
+    pub healthy: AtomicBool,
+    pub connections: AtomicUsize,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self {
+            healthy: AtomicBool::new(true),
+            connections: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Backend {
+    pub fn connect(self: &Arc<Self>) -> Connection {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        Connection(self.clone())
+    }
+
+    fn set_healthy(&self, origin: SocketAddr, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                info!("backend {origin} is healthy");
+            } else {
+                warn!("backend {origin} is unhealthy");
+            }
+        }
+    }
+}
+
+pub struct Connection(Arc<Backend>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Server {
+    pub fn backend(&self, origin: SocketAddr) -> Arc<Backend> {
+        self.backends.entry(origin).or_default().clone()
+    }
+
+    pub fn mark_unhealthy(&self, origin: SocketAddr) {
+        self.backend(origin).set_healthy(origin, false);
+    }
+
+    pub fn candidates(&self, route: &Route, protocol: i32) -> Vec<SocketAddr> {
+        let mut candidates = route
+            .origins(protocol)
+            .iter()
+            .map(|origin| (*origin, self.backend(*origin)))
+            .filter(|(_, backend)| backend.healthy.load(Ordering::Relaxed))
+            .collect::<Vec<_>>();
+        match route.policy {
+            Policy::FirstHealthy => {}
+            Policy::RoundRobin => {
+                let len = candidates.len();
+                if len > 0 {
+                    candidates.rotate_left(route.next.fetch_add(1, Ordering::Relaxed) % len);
+                }
+            }
+            Policy::LeastConnections => {
+                candidates.sort_by_key(|(_, backend)| backend.connections.load(Ordering::Relaxed))
+            }
+        }
+        candidates.into_iter().map(|(origin, _)| origin).collect()
+    }
+
+    pub async fn connect(
+        &self,
+        route: &Route,
+        protocol: i32,
+        hostname: &str,
+        metrics: &RouteMetrics,
+    ) -> Option<(SocketAddr, Connection, TcpStream)> {
+        let timeout = Duration::from_secs(self.health_check.timeout_secs);
+        for candidate in self.candidates(route, protocol) {
+            let connect = time::timeout(timeout, TcpStream::connect(candidate)).await;
+            match connect.unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())) {
+                Ok(origin) => return Some((candidate, self.backend(candidate).connect(), origin)),
+                Err(error) => {
+                    warn!("failed to connect to {candidate} for {hostname}: {error}");
+                    inc(&metrics.connect_failures);
+                    self.mark_unhealthy(candidate);
+                }
+            }
+        }
+        None
+    }
+
+    async fn check(&self, origin: SocketAddr) -> bool {
+        let timeout = Duration::from_secs(self.health_check.timeout_secs);
+        let check = async {
+            let mut stream = TcpStream::connect(origin).await?;
+            if let HealthCheck::Status = self.health_check.kind {
+                let hostname = origin.ip().to_string();
+                request_status(&mut stream, -1, &hostname, origin.port()).await?;
+            }
+            anyhow::Ok(())
+        };
+        matches!(time::timeout(timeout, check).await, Ok(Ok(())))
+    }
+
+    pub async fn health_check(self: Arc<Self>) {
+        let interval_secs = self.health_check.interval_secs.max(1);
+        let mut interval = time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            let origins = self
+                .routes
+                .load()
+                .iter()
+                .flat_map(|route| route.all_origins().collect::<Vec<_>>())
+                .collect::<HashSet<_>>();
+            self.backends.retain(|origin, backend| {
+                origins.contains(origin) || backend.connections.load(Ordering::Relaxed) > 0
+            });
+            let checks = origins.into_iter().map(|origin| {
+                let server = self.clone();
+                tokio::spawn(async move {
+                    let healthy = server.check(origin).await;
+                    server.backend(origin).set_healthy(origin, healthy);
+                })
+            });
+            for check in checks.collect::<Vec<_>>() {
+                let _ = check.await;
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    Server,
+    api::error,
+    auth::{Auth, Operation},
+    cidr::{self, Cidr},
+    now,
+    persist::Mutation,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
+use tracing::info;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ban {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl Ban {
+    fn expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub struct BanRequest {
+    duration_secs: Option<u64>,
+}
+
+impl Server {
+    pub fn admits(&self, addr: IpAddr) -> bool {
+        let now = now();
+        cidr::admits(&self.allowed_ips, &self.denied_ips, addr)
+            && !self
+                .bans
+                .iter()
+                .any(|ban| ban.key().contains(addr) && !ban.expired(now))
+    }
+
+    pub async fn list_bans(State(server): State<Arc<Server>>, auth: Auth) -> Response {
+        if let Some(response) = auth.deny(Operation::Read, "*") {
+            return response;
+        }
+        let now = now();
+        server.bans.retain(|_, ban| !ban.expired(now));
+        let bans = server
+            .bans
+            .iter()
+            .map(|ban| (ban.key().to_string(), ban.value().clone()))
+            .collect::<BTreeMap<_, _>>();
+        Json(bans).into_response()
+    }
+
+    pub async fn put_ban(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Path(cidr): Path<String>,
+        request: Option<Json<BanRequest>>,
+    ) -> Response {
+        if let Some(response) = auth.deny(Operation::Register, "*") {
+            return response;
+        }
+        let cidr = match cidr.parse::<Cidr>() {
+            Ok(cidr) => cidr,
+            Err(invalid) => return error(StatusCode::BAD_REQUEST, invalid),
+        };
+        let Json(BanRequest { duration_secs }) = request.unwrap_or_default();
+        let ban = Ban {
+            expires_at: duration_secs.map(|duration_secs| now() + duration_secs),
+        };
+        info!("banned {cidr}");
+        server.bans.insert(cidr, ban.clone());
+        server
+            .persist(Mutation::Ban {
+                cidr,
+                ban: ban.clone(),
+            })
+            .await;
+        Json(ban).into_response()
+    }
+
+    pub async fn delete_ban(
+        State(server): State<Arc<Server>>,
+        auth: Auth,
+        Path(cidr): Path<String>,
+    ) -> Response {
+        if let Some(response) = auth.deny(Operation::Delete, "*") {
+            return response;
+        }
+        let cidr = match cidr.parse::<Cidr>() {
+            Ok(cidr) => cidr,
+            Err(invalid) => return error(StatusCode::BAD_REQUEST, invalid),
+        };
+        match server.bans.remove(&cidr) {
+            Some(_) => {
+                info!("unbanned {cidr}");
+                server.persist(Mutation::Unban { cidr }).await;
+                StatusCode::NO_CONTENT.into_response()
+            }
+            None => error(StatusCode::NOT_FOUND, format!("no ban for {cidr}")),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use md5::{Digest, Md5};
+use std::net::SocketAddr;
+
+pub fn offline_uuid(name: &str) -> String {
+    let mut uuid: [u8; 16] = Md5::digest(format!("OfflinePlayer:{name}")).into();
+    uuid[6] = uuid[6] & 0x0F | 0x30;
+    uuid[8] = uuid[8] & 0x3F | 0x80;
+    uuid.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn forward_hostname(hostname: &str, addr: SocketAddr, name: &str) -> String {
+    let hostname = hostname
+        .split_once('\0')
+        .map_or(hostname, |(hostname, _)| hostname);
+    format!(
+        "{hostname}\0{}\0{}",
+        addr.ip().to_canonical(),
+        offline_uuid(name)
+    )
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Error, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    net::IpAddr,
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(addr) & mask).into())
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(addr) & mask).into())
+        }
+    }
+}
+
+impl Cidr {
+    pub fn new(addr: IpAddr, prefix: u8) -> Self {
+        let addr = addr.to_canonical();
+        Self {
+            addr: mask(addr, prefix),
+            prefix,
+        }
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        addr.is_ipv4() == self.addr.is_ipv4() && mask(addr, self.prefix) == self.addr
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(cidr: &str) -> Result<Self> {
+        let (addr, prefix) = match cidr.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>()?, Some(prefix)),
+            None => (cidr.parse::<IpAddr>()?, None),
+        };
+        let addr = addr.to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().context("invalid prefix length")?,
+            None => max,
+        };
+        if prefix > max {
+            bail!("prefix length {prefix} is too long");
+        }
+        Ok(Self::new(addr, prefix))
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = Error;
+
+    fn try_from(cidr: String) -> Result<Self> {
+        cidr.parse()
+    }
+}
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+pub fn admits(allowed: &[Cidr], denied: &[Cidr], addr: IpAddr) -> bool {
+    !denied.iter().any(|cidr| cidr.contains(addr))
+        && (allowed.is_empty() || allowed.iter().any(|cidr| cidr.contains(addr)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(cidr: &str) -> Cidr {
+        cidr.parse().unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_masks() {
+        assert_eq!(cidr("10.1.2.3/8").to_string(), "10.0.0.0/8");
+        assert_eq!(cidr("10.1.2.3").to_string(), "10.1.2.3/32");
+        assert_eq!(cidr("2001:db8::1/32").to_string(), "2001:db8::/32");
+        assert_eq!(cidr("2001:db8::1").to_string(), "2001:db8::1/128");
+        assert_eq!(cidr("::ffff:10.1.2.3/8").to_string(), "10.0.0.0/8");
+    }
+
+    #[test]
+    fn prefix_bounds() {
+        assert_eq!(cidr("10.1.2.3/0").to_string(), "0.0.0.0/0");
+        assert_eq!(cidr("10.1.2.3/32").to_string(), "10.1.2.3/32");
+        assert_eq!(cidr("2001:db8::1/0").to_string(), "::/0");
+        assert_eq!(cidr("2001:db8::1/128").to_string(), "2001:db8::1/128");
+        assert!("10.1.2.3/33".parse::<Cidr>().is_err());
+        assert!("2001:db8::1/129".parse::<Cidr>().is_err());
+        assert!("10.1.2.3/-1".parse::<Cidr>().is_err());
+        assert!("10.1.2.3/".parse::<Cidr>().is_err());
+        assert!("example.com/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn contains() {
+        assert!(cidr("10.0.0.0/8").contains(ip("10.255.0.1")));
+        assert!(!cidr("10.0.0.0/8").contains(ip("11.0.0.1")));
+        assert!(cidr("0.0.0.0/0").contains(ip("1.2.3.4")));
+        assert!(!cidr("0.0.0.0/0").contains(ip("2001:db8::1")));
+        assert!(cidr("::/0").contains(ip("2001:db8::1")));
+        assert!(cidr("2001:db8::/32").contains(ip("2001:db8:ffff::1")));
+        assert!(!cidr("2001:db8::/32").contains(ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn contains_mapped() {
+        assert!(cidr("10.0.0.0/8").contains(ip("::ffff:10.1.2.3")));
+        assert!(cidr("::ffff:10.0.0.0/8").contains(ip("10.1.2.3")));
+        assert!(!cidr("::/0").contains(ip("::ffff:10.1.2.3")));
+    }
+
+    #[test]
+    fn admits_lists() {
+        let allowed = [cidr("10.0.0.0/8")];
+        let denied = [cidr("10.0.0.1")];
+        assert!(admits(&[], &[], ip("1.2.3.4")));
+        assert!(admits(&allowed, &denied, ip("10.0.0.2")));
+        assert!(!admits(&allowed, &denied, ip("10.0.0.1")));
+        assert!(!admits(&allowed, &denied, ip("1.2.3.4")));
+        assert!(!admits(&[], &denied, ip("::ffff:10.0.0.1")));
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use socket2::{SockRef, TcpKeepalive};
+use std::{future::pending, io, time::Duration};
+use tokio::{net::TcpStream, time};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeepaliveConfig {
+    pub time_secs: u64,
+    pub interval_secs: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_idle_timeout_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend_idle_timeout_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<KeepaliveConfig>,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            client_idle_timeout_secs: None,
+            backend_idle_timeout_secs: None,
+            keepalive: Some(KeepaliveConfig {
+                time_secs: 60,
+                interval_secs: 10,
+            }),
+        }
+    }
+}
+
+impl ConnectionConfig {
+    pub fn configure(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        if let Some(keepalive) = &self.keepalive {
+            let keepalive = TcpKeepalive::new()
+                .with_time(Duration::from_secs(keepalive.time_secs))
+                .with_interval(Duration::from_secs(keepalive.interval_secs));
+            SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn sleep(secs: Option<u64>) {
+    match secs {
+        Some(secs) => time::sleep(Duration::from_secs(secs)).await,
+        None => pending().await,
+    }
+}
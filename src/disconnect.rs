@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisconnectConfig {
+    pub unknown_host: String,
+    pub offline: String,
+    pub denied: String,
+    pub unsupported_version: String,
+}
+
+impl Default for DisconnectConfig {
+    fn default() -> Self {
+        Self {
+            unknown_host: "Unknown host {hostname}".to_string(),
+            offline: "{hostname} is offline".to_string(),
+            denied: "You are not allowed to join {hostname}".to_string(),
+            unsupported_version:
+                "{hostname} requires Minecraft {versions}, please {change} to {version}".to_string(),
+        }
+    }
+}
+
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |message, (key, val)| {
+            message.replace(&format!("{{{key}}}"), val)
+        })
+}
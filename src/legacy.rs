@@ -0,0 +1,119 @@
+use crate::{
+    Server, cidr,
+    route::{self, normalize},
+    status::Status,
+};
+use anyhow::Result;
+use serde_json::Value;
+use std::net::SocketAddr;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::Instant,
+};
+use tracing::{debug, info};
+
+const PING: u8 = 0xFE;
+const PAYLOAD: u8 = 0x01;
+const PLUGIN_MESSAGE: u8 = 0xFA;
+const KICK: u8 = 0xFF;
+const PROTOCOL: i32 = 127;
+
+pub async fn is_ping(stream: &TcpStream) -> Result<bool> {
+    let mut buf = [0; 3];
+    let n = stream.peek(&mut buf).await?;
+    Ok(matches!(
+        buf[..n],
+        [PING] | [PING, PAYLOAD] | [PING, PAYLOAD, PLUGIN_MESSAGE]
+    ))
+}
+
+async fn read_string<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String> {
+    let len = reader.read_u16().await?;
+    let mut chars = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        chars.push(reader.read_u16().await?);
+    }
+    Ok(String::from_utf16(&chars)?)
+}
+
+async fn read_hostname(stream: &mut TcpStream) -> Result<Option<String>> {
+    let mut buf = [0; 3];
+    let n = stream.peek(&mut buf).await?;
+    stream.read_exact(&mut buf[..n]).await?;
+    if n < 3 {
+        return Ok(None);
+    }
+    read_string(stream).await?;
+    stream.read_u16().await?;
+    stream.read_u8().await?;
+    let hostname = read_string(stream).await?;
+    stream.read_i32().await?;
+    Ok(Some(hostname))
+}
+
+fn text(description: &Value) -> String {
+    match description {
+        Value::String(text) => text.clone(),
+        Value::Array(components) => components.iter().map(text).collect(),
+        Value::Object(component) => {
+            let mut out = component.get("text").map(text).unwrap_or_default();
+            if let Some(extra) = component.get("extra") {
+                out.push_str(&text(extra));
+            }
+            out
+        }
+        _ => String::new(),
+    }
+}
+
+pub async fn write_kick(stream: &mut TcpStream, status: &Status) -> Result<()> {
+    let version = status.version.clone().unwrap_or_default();
+    let players = status.players.clone().unwrap_or_default();
+    let reason = format!(
+        "§1\0{}\0{}\0{}\0{}\0{}",
+        version.protocol,
+        version.name,
+        text(&status.description),
+        players.online,
+        players.max
+    );
+    let chars = reason.encode_utf16().collect::<Vec<_>>();
+    let mut kick = Vec::with_capacity(3 + chars.len() * 2);
+    kick.push(KICK);
+    kick.extend_from_slice(&(chars.len() as u16).to_be_bytes());
+    for char in chars {
+        kick.extend_from_slice(&char.to_be_bytes());
+    }
+    stream.write_all(&kick).await?;
+    Ok(())
+}
+
+impl Server {
+    pub async fn legacy_ping(
+        &self,
+        mut edge: TcpStream,
+        addr: SocketAddr,
+        deadline: Instant,
+    ) -> Result<()> {
+        let hostname = self.within(deadline, read_hostname(&mut edge)).await?;
+        let hostname = hostname.as_deref().map(normalize);
+        let route = hostname
+            .as_ref()
+            .and_then(|hostname| route::lookup(&self.routes.load(), hostname));
+        let hostname = hostname.as_deref().unwrap_or("unknown hostname");
+        if let Some((_, route)) = &route
+            && !cidr::admits(&route.allowed_ips, &route.denied_ips, addr.ip())
+        {
+            debug!("rejected legacy ping from {addr} to {hostname}");
+            return Ok(());
+        }
+        info!("legacy ping from {addr} to {hostname}");
+        let status = route
+            .as_ref()
+            .and_then(|(_, route)| route.status.as_ref())
+            .unwrap_or(&self.status)
+            .status(PROTOCOL);
+        write_kick(&mut edge, &status).await
+    }
+}
@@ -1,117 +1,234 @@
+mod api;
+mod auth;
+mod backend;
+mod ban;
+mod bungee;
+mod cidr;
+mod connection;
+mod disconnect;
+mod legacy;
+mod metrics;
+mod persist;
+mod protocol;
+mod proxy_protocol;
+mod ratelimit;
+mod reload;
+mod route;
+mod session;
+mod shutdown;
+mod status;
+mod version;
+
 use anyhow::{Result, bail};
-use axum::{
-    Router,
-    extract::{ConnectInfo, Path, Query, State},
-    response::{IntoResponse, Redirect, Response},
-    routing::get,
-};
+use arc_swap::ArcSwap;
+use auth::TokenConfig;
+use backend::{Backend, HealthCheckConfig};
+use ban::Ban;
+use cidr::Cidr;
+use connection::ConnectionConfig;
 use dashmap::DashMap;
+use disconnect::{DisconnectConfig, render};
+use metrics::{Metrics, RouteMetrics, inc};
+use protocol::{
+    Handshake, HandshakeConfig, LOGIN, LoginStart, STATUS, TRANSFER, TooLong, read_packet,
+    serve_status, write_disconnect, write_packet,
+};
+use ratelimit::{Permit, RateLimitConfig, RateLimiter};
+use route::Route;
 use serde::{Deserialize, Serialize};
+use session::{Session, Sessions};
+use shutdown::{ShutdownConfig, Tracker};
+use status::{Status, StatusCacheConfig, StatusConfig, StatusKey};
 use std::{
-    io::{self, Cursor},
+    collections::HashMap,
+    io,
     net::{Ipv6Addr, SocketAddr},
-    sync::Arc,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
     fs,
     io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::{TcpListener, TcpStream},
-    select, signal, spawn,
+    select, spawn,
+    sync::Mutex,
+    time::{Instant, timeout_at},
 };
-use tracing::{info, warn};
-
-const SEGMENT_BITS: u8 = 0x7F;
-const CONTINUE_BIT: u8 = 0x80;
-
-async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i32> {
-    let mut tmp = CONTINUE_BIT;
-    let mut val = 0;
-    let mut pos = 0;
-    while tmp & CONTINUE_BIT == CONTINUE_BIT {
-        tmp = reader.read_u8().await?;
-        val = val | ((tmp & SEGMENT_BITS) as i32) << pos;
-        pos = pos + 7;
-        if pos >= 32 {
-            bail!("varint too long");
-        }
-    }
-    Ok(val)
-}
-
-async fn read_string<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String> {
-    let mut buf = vec![0; read_varint(reader).await? as usize];
-    reader.read_exact(&mut buf).await?;
-    Ok(String::from_utf8(buf)?)
-}
-
-async fn write_varint<W: AsyncWrite + Unpin>(writer: &mut W, mut val: i32) -> Result<()> {
-    loop {
-        let tmp = val as u8 & SEGMENT_BITS;
-        val = val >> 7;
-        if val > 0 {
-            writer.write_u8(tmp | CONTINUE_BIT).await?;
-        } else {
-            writer.write_u8(tmp).await?;
-            break;
-        }
-    }
-    Ok(())
-}
+use tracing::{debug, info, warn};
 
-async fn pipe<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(mut reader: R, mut writer: W) {
+async fn pipe<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    mut reader: R,
+    mut writer: W,
+    bytes: [&AtomicU64; 2],
+    idle_timeout_secs: Option<u64>,
+) -> bool {
     let mut buf = [0; 1536];
-    loop {
-        match reader.read(&mut buf).await {
-            Ok(0) => break,
+    let idle = loop {
+        let n = select! {
+            result = reader.read(&mut buf) => result,
+            _ = connection::sleep(idle_timeout_secs) => break true,
+        };
+        match n {
+            Ok(0) => break false,
             Ok(n) => {
                 if writer.write_all(&buf[..n]).await.is_err() {
-                    break;
+                    break false;
+                }
+                for bytes in bytes {
+                    bytes.fetch_add(n as u64, Ordering::Relaxed);
                 }
             }
-            Err(_) => break,
+            Err(_) => break false,
         }
-    }
+    };
     let _ = writer.shutdown().await;
+    idle
 }
 
-async fn pipe_stream(a: TcpStream, b: TcpStream) -> Result<()> {
-    a.set_nodelay(true)?;
-    b.set_nodelay(true)?;
+async fn pipe_stream(
+    a: TcpStream,
+    b: TcpStream,
+    config: &ConnectionConfig,
+    max_session_secs: Option<u64>,
+    metrics: &RouteMetrics,
+    session: &Session,
+) -> Result<()> {
+    config.configure(&a)?;
+    config.configure(&b)?;
     let (a_reader, a_writer) = a.into_split();
     let (b_reader, b_writer) = b.into_split();
+    let upstream = pipe(
+        a_reader,
+        b_writer,
+        [&metrics.bytes_in, &session.bytes_in],
+        config.client_idle_timeout_secs,
+    );
+    let downstream = pipe(
+        b_reader,
+        a_writer,
+        [&metrics.bytes_out, &session.bytes_out],
+        config.backend_idle_timeout_secs,
+    );
+    let Session { id, addr, .. } = session;
     select! {
-        _ = pipe(a_reader, b_writer) => {},
-        _ = pipe(b_reader, a_writer) => {},
+        idle = upstream => {
+            if idle {
+                info!("closing idle session {id} from {addr}, the player stopped sending");
+            }
+        },
+        idle = downstream => {
+            if idle {
+                info!("closing idle session {id} from {addr}, the backend stopped sending");
+            }
+        },
+        _ = connection::sleep(max_session_secs) => {
+            info!("closing session {id} from {addr} after its maximum duration");
+        },
+        _ = session.kick.notified() => {},
     }
     Ok(())
 }
 
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
 #[derive(Serialize, Deserialize)]
 struct Server {
-    routes: DashMap<String, SocketAddr>,
+    routes: ArcSwap<DashMap<String, Arc<Route>>>,
     minecraft_proxy: SocketAddr,
     http_api_server: SocketAddr,
+    #[serde(default)]
+    status: StatusConfig,
+    #[serde(default)]
+    disconnect: DisconnectConfig,
+    #[serde(default)]
+    tokens: HashMap<String, TokenConfig>,
+    #[serde(default)]
+    accept_proxy_protocol: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    trusted_proxies: Vec<Cidr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allowed_ips: Vec<Cidr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    denied_ips: Vec<Cidr>,
+    #[serde(default)]
+    bans: DashMap<Cidr, Ban>,
+    #[serde(default)]
+    rate_limit: RateLimitConfig,
+    #[serde(default)]
+    handshake: HandshakeConfig,
+    #[serde(default)]
+    connection: ConnectionConfig,
+    #[serde(default)]
+    status_cache: StatusCacheConfig,
+    #[serde(default)]
+    health_check: HealthCheckConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    journal: Option<PathBuf>,
+    #[serde(default)]
+    shutdown: ShutdownConfig,
+    #[serde(skip)]
+    backends: DashMap<SocketAddr, Arc<Backend>>,
+    #[serde(skip)]
+    saved: Mutex<Option<SystemTime>>,
+    #[serde(skip)]
+    connections: Arc<Tracker>,
+    #[serde(skip)]
+    draining: AtomicBool,
+    #[serde(skip)]
+    metrics: Metrics,
+    #[serde(skip)]
+    sessions: Sessions,
+    #[serde(skip)]
+    rate_limiter: RateLimiter,
+    #[serde(skip)]
+    statuses: DashMap<StatusKey, (Instant, Status)>,
 }
 
 impl Default for Server {
     fn default() -> Self {
         info!("using default config");
         Self {
-            routes: DashMap::new(),
+            routes: ArcSwap::default(),
             minecraft_proxy: SocketAddr::from((Ipv6Addr::UNSPECIFIED, 25565)),
             http_api_server: SocketAddr::from((Ipv6Addr::LOCALHOST, 80)),
+            status: StatusConfig::default(),
+            disconnect: DisconnectConfig::default(),
+            tokens: HashMap::new(),
+            accept_proxy_protocol: false,
+            trusted_proxies: Vec::new(),
+            allowed_ips: Vec::new(),
+            denied_ips: Vec::new(),
+            bans: DashMap::new(),
+            rate_limit: RateLimitConfig::default(),
+            handshake: HandshakeConfig::default(),
+            connection: ConnectionConfig::default(),
+            status_cache: StatusCacheConfig::default(),
+            health_check: HealthCheckConfig::default(),
+            journal: None,
+            shutdown: ShutdownConfig::default(),
+            backends: DashMap::new(),
+            saved: Mutex::default(),
+            connections: Arc::default(),
+            draining: AtomicBool::new(false),
+            metrics: Metrics::default(),
+            sessions: Sessions::default(),
+            rate_limiter: RateLimiter::default(),
+            statuses: DashMap::new(),
         }
     }
 }
 
-#[derive(Deserialize)]
-struct Register {
-    redirect_url: Option<String>,
-}
-
 impl Server {
     async fn new() -> Result<Arc<Server>> {
-        Ok(Arc::new(match fs::read_to_string("config.json").await {
+        let server: Server = match fs::read_to_string("config.json").await {
             Ok(routes) => {
                 info!("reading config from config.json");
                 serde_json::from_str(&routes)?
@@ -124,55 +241,235 @@ impl Server {
                     bail!("error while reading config.json: {error}");
                 }
             }
-        }))
+        };
+        server.replay().await?;
+        Ok(Arc::new(server))
     }
 
-    async fn proxy(self: Arc<Self>, mut edge: TcpStream, addr: SocketAddr) -> Result<()> {
-        let mut packet = vec![0; read_varint(&mut edge).await? as usize];
-        edge.read_exact(&mut packet).await?;
-        let mut packet = Cursor::new(packet);
-        if read_varint(&mut packet).await? != 0 {
+    async fn proxy(
+        self: Arc<Self>,
+        mut edge: TcpStream,
+        mut addr: SocketAddr,
+        permit: Option<Permit>,
+    ) -> Result<()> {
+        let deadline = self.deadline();
+        let _permit = match permit {
+            Some(permit) => permit,
+            None => {
+                let source = self.within(deadline, proxy_protocol::read(&mut edge));
+                if let Some(source) = source.await? {
+                    addr = source;
+                }
+                let Some(permit) = self.accept(addr) else {
+                    return Ok(());
+                };
+                permit
+            }
+        };
+        if self.within(deadline, legacy::is_ping(&edge)).await? {
+            return self.legacy_ping(edge, addr, deadline).await;
+        }
+        let max_packet_length = self.handshake.max_packet_length;
+        let handshake = async {
+            let mut packet = read_packet(&mut edge, max_packet_length).await?;
+            let handshake = Handshake::read(&mut packet).await?;
+            if let Some(handshake) = &handshake
+                && handshake.hostname.len() > self.handshake.max_hostname_length
+            {
+                bail!(TooLong("hostname"));
+            }
+            anyhow::Ok(handshake.map(|handshake| (packet, handshake)))
+        };
+        let (packet, handshake) = match self.within(deadline, handshake).await {
+            Ok(Some(handshake)) => handshake,
+            Err(error) if error.is::<TooLong>() => {
+                inc(&self.metrics.oversized_handshakes);
+                return Err(error);
+            }
+            result => {
+                inc(&self.metrics.handshake_errors);
+                return result.map(|_| ());
+            }
+        };
+        let protocol = handshake.protocol;
+        let hostname = route::normalize(&handshake.hostname);
+        let route = route::lookup(&self.routes.load(), &hostname);
+        if let Some((_, route)) = &route
+            && !cidr::admits(&route.allowed_ips, &route.denied_ips, addr.ip())
+        {
+            debug!("rejected connection from {addr} to {hostname}");
             return Ok(());
         }
-        let protocol = read_varint(&mut packet).await?;
-        let hostname = read_string(&mut packet).await?;
-        let origin = self
-            .routes
-            .get(hostname.as_str())
-            .map(|origin| origin.clone());
         info!("new connection from {addr} to {hostname} using {protocol}");
-        let Some(origin) = origin else {
-            return Ok(());
+        let login = if matches!(handshake.next_state, LOGIN | TRANSFER) {
+            let login_start = async {
+                let mut packet = read_packet(&mut edge, max_packet_length).await?;
+                let login_start = LoginStart::read(&mut packet, protocol).await?;
+                anyhow::Ok((packet, login_start))
+            };
+            let (packet, login_start) = self.within(deadline, login_start).await?;
+            let LoginStart { name, uuid } = &login_start;
+            let uuid = uuid.as_deref().unwrap_or("no uuid");
+            info!("{name} ({uuid}) is logging in from {addr} to {hostname}");
+            Some((packet, login_start))
+        } else {
+            None
+        };
+        if self.draining.load(Ordering::Relaxed)
+            && let Some(message) = &self.shutdown.message
+            && login.is_some()
+        {
+            let reason = render(message, &[("hostname", &hostname)]);
+            return write_disconnect(&mut edge, &reason).await;
+        }
+        let Some((pattern, route)) = route else {
+            inc(&self.metrics.unknown_hostnames);
+            return self.fallback(edge, &handshake, &hostname, None).await;
         };
-        let packet = packet.into_inner();
-        let mut origin = TcpStream::connect(origin).await?;
-        write_varint(&mut origin, packet.len() as i32).await?;
-        origin.write_all(&packet).await?;
-        pipe_stream(edge, origin).await?;
+        if let Some((_, login_start)) = &login
+            && !route.admits(login_start)
+        {
+            info!("denied {} access to {hostname}", login_start.name);
+            let reason = render(
+                &self.disconnect.denied,
+                &[("hostname", &hostname), ("username", &login_start.name)],
+            );
+            return write_disconnect(&mut edge, &reason).await;
+        }
+        if let Some((_, login_start)) = &login
+            && let Some(protocols) = &route.protocols
+            && let Some((change, version)) = protocols.target(protocol)
+        {
+            info!(
+                "denied {} access to {hostname} using {protocol}",
+                login_start.name
+            );
+            let reason = render(
+                &self.disconnect.unsupported_version,
+                &[
+                    ("hostname", &hostname),
+                    ("username", &login_start.name),
+                    ("versions", &protocols.describe()),
+                    ("change", change),
+                    ("version", &version),
+                ],
+            );
+            return write_disconnect(&mut edge, &reason).await;
+        }
+        let metrics = self.metrics.route(&pattern);
+        inc(&metrics.connections);
+        if handshake.next_state == STATUS {
+            let local = edge.local_addr()?;
+            let status = match route.aggregate {
+                Some(_) => Some(
+                    self.aggregate_status(&pattern, &route, &handshake, &metrics, addr, local)
+                        .await,
+                ),
+                None => {
+                    self.route_status(&pattern, &route, &handshake, &metrics, addr, local)
+                        .await
+                }
+            };
+            let mut status = status.unwrap_or_else(|| {
+                route
+                    .status
+                    .as_ref()
+                    .unwrap_or(&self.status)
+                    .status(protocol)
+            });
+            if let Some(protocols) = &route.protocols {
+                protocols.gate(&mut status, protocol);
+            }
+            let status = serde_json::to_string(&status)?;
+            return self
+                .within(self.deadline(), serve_status(&mut edge, &status))
+                .await;
+        }
+        let connection = self.connect(&route, protocol, &hostname, &metrics).await;
+        let Some((backend, _connection, mut origin)) = connection else {
+            return self
+                .fallback(edge, &handshake, &hostname, Some(&route))
+                .await;
+        };
+        if let Some(proxy_protocol) = route.proxy_protocol {
+            let header = proxy_protocol.encode(addr, edge.local_addr()?);
+            origin.write_all(&header).await?;
+        }
+        match &login {
+            Some((login_start, LoginStart { name, .. })) if route.bungee_forwarding => {
+                let handshake = Handshake {
+                    hostname: bungee::forward_hostname(&handshake.hostname, addr, name),
+                    ..handshake
+                };
+                write_packet(&mut origin, &handshake.encode().await?).await?;
+                write_packet(&mut origin, login_start.get_ref()).await?;
+            }
+            Some((login_start, _)) => {
+                write_packet(&mut origin, packet.get_ref()).await?;
+                write_packet(&mut origin, login_start.get_ref()).await?;
+            }
+            None => write_packet(&mut origin, packet.get_ref()).await?,
+        }
+        let _active = metrics.activate();
+        let login_start = login.as_ref().map(|(_, login_start)| login_start);
+        let session = self
+            .sessions
+            .open(addr, hostname, protocol, backend, login_start);
+        pipe_stream(
+            edge,
+            origin,
+            &self.connection,
+            route.max_session_secs,
+            &metrics,
+            &session.session,
+        )
+        .await?;
         Ok(())
     }
 
-    async fn register(
-        State(server): State<Arc<Server>>,
-        Query(Register { redirect_url }): Query<Register>,
-        Path(hostname): Path<String>,
-        ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    ) -> Response {
-        let origin = SocketAddr::new(addr.ip(), 25565);
-        info!("registered route {hostname} to {origin}");
-        server.routes.insert(hostname, origin);
-        if let Some(redirect_url) = redirect_url {
-            Redirect::to(redirect_url.as_str()).into_response()
-        } else {
-            ().into_response()
-        }
+    fn deadline(&self) -> Instant {
+        Instant::now() + Duration::from_secs(self.handshake.timeout_secs)
     }
 
-    async fn shutdown(self: Arc<Self>) -> Result<()> {
-        signal::ctrl_c().await?;
-        info!("gracefully shutting down");
-        fs::write("config.json", serde_json::to_vec_pretty(self.as_ref())?).await?;
-        Ok(())
+    async fn within<T>(
+        &self,
+        deadline: Instant,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let Ok(result) = timeout_at(deadline, future).await else {
+            inc(&self.metrics.handshake_timeouts);
+            bail!("handshake timed out");
+        };
+        result
+    }
+
+    async fn fallback(
+        &self,
+        mut edge: TcpStream,
+        handshake: &Handshake,
+        hostname: &str,
+        route: Option<&Route>,
+    ) -> Result<()> {
+        match handshake.next_state {
+            STATUS => {
+                let status = route
+                    .and_then(|route| route.status.as_ref())
+                    .unwrap_or(&self.status)
+                    .status(handshake.protocol);
+                let status = serde_json::to_string(&status)?;
+                self.within(self.deadline(), serve_status(&mut edge, &status))
+                    .await
+            }
+            LOGIN | TRANSFER => {
+                let template = match route {
+                    Some(_) => &self.disconnect.offline,
+                    None => &self.disconnect.unknown_host,
+                };
+                let reason = render(template, &[("hostname", hostname)]);
+                write_disconnect(&mut edge, &reason).await
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -182,15 +479,39 @@ async fn main() {
     let server = Server::new().await.expect("failed to create server");
     select! {
         result = server.clone().shutdown() => result.expect("error while shutting down"),
+        _ = server.clone().health_check() => {},
+        _ = server.clone().watch() => {},
+        _ = server.clone().prune_rate_limits() => {},
         _ = async {
             let listener = TcpListener::bind(server.minecraft_proxy)
                 .await
                 .expect("failed to create listener");
             info!("minecraft proxy started on {:?}", server.minecraft_proxy);
+            if server.accept_proxy_protocol && server.trusted_proxies.is_empty() {
+                warn!("no trusted proxies configured, proxy protocol headers are ignored");
+            }
             while let Ok((conn, addr)) = listener.accept().await {
+                if server.draining.load(Ordering::Relaxed) && server.shutdown.message.is_none() {
+                    continue;
+                }
+                let trusted = server.accept_proxy_protocol
+                    && server
+                        .trusted_proxies
+                        .iter()
+                        .any(|cidr| cidr.contains(addr.ip()));
+                let permit = if trusted {
+                    None
+                } else {
+                    let Some(permit) = server.accept(addr) else {
+                        continue;
+                    };
+                    Some(permit)
+                };
                 let server = server.clone();
+                let tracked = server.connections.track();
                 spawn(async move {
-                    let Err(error) = server.proxy(conn, addr).await else {
+                    let _tracked = tracked;
+                    let Err(error) = server.proxy(conn, addr, permit).await else {
                         return;
                     };
                     warn!("error while proxying connection from {addr}: {error}");
@@ -202,9 +523,12 @@ async fn main() {
                 .await
                 .expect("failed to create listener");
             info!("http api server started on {:?}", server.http_api_server);
-            let router = Router::new()
-                .route("/register/{hostname}", get(Server::register))
-                .with_state(server.clone())
+            if server.tokens.is_empty() {
+                warn!("no api tokens configured, the http api is unauthenticated");
+            }
+            let router = server
+                .clone()
+                .router()
                 .into_make_service_with_connect_info::<SocketAddr>();
             axum::serve(listener, router).await.expect("error while serving http")
         } => {},
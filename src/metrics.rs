@@ -0,0 +1,175 @@
+use crate::{
+    Server,
+    auth::{Auth, Operation},
+};
+use axum::{extract::State, http::header::CONTENT_TYPE, response::IntoResponse};
+use dashmap::DashMap;
+use std::{
+    fmt::Write,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+#[derive(Default)]
+pub struct RouteMetrics {
+    pub active: AtomicU64,
+    pub connections: AtomicU64,
+    pub bytes_in: AtomicU64,
+    pub bytes_out: AtomicU64,
+    pub connect_failures: AtomicU64,
+}
+
+impl RouteMetrics {
+    pub fn activate(self: &Arc<Self>) -> Active {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Active(self.clone())
+    }
+}
+
+pub struct Active(Arc<RouteMetrics>);
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    pub routes: DashMap<String, Arc<RouteMetrics>>,
+    pub unknown_hostnames: AtomicU64,
+    pub handshake_errors: AtomicU64,
+    pub handshake_timeouts: AtomicU64,
+    pub oversized_handshakes: AtomicU64,
+    pub rate_limited_ip: AtomicU64,
+    pub rate_limited_prefix: AtomicU64,
+    pub connection_limited: AtomicU64,
+}
+
+pub fn inc(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+type Field = fn(&RouteMetrics) -> &AtomicU64;
+
+const FAMILIES: [(&str, &str, &str, Field); 5] = [
+    (
+        "hopper_active_connections",
+        "gauge",
+        "Connections currently proxied to the route",
+        |metrics| &metrics.active,
+    ),
+    (
+        "hopper_connections_total",
+        "counter",
+        "Connections routed to the route",
+        |metrics| &metrics.connections,
+    ),
+    (
+        "hopper_bytes_in_total",
+        "counter",
+        "Bytes received from players and sent to the route",
+        |metrics| &metrics.bytes_in,
+    ),
+    (
+        "hopper_bytes_out_total",
+        "counter",
+        "Bytes received from the route and sent to players",
+        |metrics| &metrics.bytes_out,
+    ),
+    (
+        "hopper_backend_connect_failures_total",
+        "counter",
+        "Failed connection attempts to backend servers of the route",
+        |metrics| &metrics.connect_failures,
+    ),
+];
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    pub fn route(&self, route: &str) -> Arc<RouteMetrics> {
+        if let Some(metrics) = self.routes.get(route) {
+            return metrics.clone();
+        }
+        self.routes.entry(route.to_string()).or_default().clone()
+    }
+
+    fn render(&self, auth: &Auth) -> String {
+        let mut routes = self
+            .routes
+            .iter()
+            .filter(|route| auth.allows(Operation::Read, route.key()))
+            .map(|route| (escape(route.key()), route.value().clone()))
+            .collect::<Vec<_>>();
+        routes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut out = String::new();
+        for (name, kind, help, metric) in FAMILIES {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+            for (route, metrics) in &routes {
+                let val = metric(metrics).load(Ordering::Relaxed);
+                let _ = writeln!(out, "{name}{{route=\"{route}\"}} {val}");
+            }
+        }
+        let globals = [
+            (
+                "hopper_unknown_hostnames_total",
+                "Handshakes for hostnames without a route",
+                &self.unknown_hostnames,
+            ),
+            (
+                "hopper_handshake_errors_total",
+                "Connections that did not send a valid handshake",
+                &self.handshake_errors,
+            ),
+            (
+                "hopper_handshake_timeouts_total",
+                "Connections that did not complete the handshake in time",
+                &self.handshake_timeouts,
+            ),
+            (
+                "hopper_oversized_handshakes_total",
+                "Handshakes exceeding the packet or hostname length limits",
+                &self.oversized_handshakes,
+            ),
+        ];
+        for (name, help, counter) in globals {
+            let val = counter.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {val}"
+            );
+        }
+        let limits = [
+            ("ip", &self.rate_limited_ip),
+            ("prefix", &self.rate_limited_prefix),
+            ("connections", &self.connection_limited),
+        ];
+        let name = "hopper_rate_limited_total";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Connections rejected by rate limits\n# TYPE {name} counter"
+        );
+        for (limit, counter) in limits {
+            let val = counter.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}{{limit=\"{limit}\"}} {val}");
+        }
+        out
+    }
+}
+
+impl Server {
+    pub async fn metrics(State(server): State<Arc<Server>>, auth: Auth) -> impl IntoResponse {
+        (
+            [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+            server.metrics.render(&auth),
+        )
+    }
+}
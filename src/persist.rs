@@ -0,0 +1,128 @@
+use crate::{Server, ban::Ban, cidr::Cidr, route::Route};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{io, path::PathBuf, sync::Arc, time::SystemTime};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+};
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Mutation {
+    Put { hostname: String, route: Arc<Route> },
+    Delete { hostname: String },
+    Ban { cidr: Cidr, ban: Ban },
+    Unban { cidr: Cidr },
+}
+
+pub async fn modified() -> Option<SystemTime> {
+    fs::metadata("config.json").await.ok()?.modified().ok()
+}
+
+impl Server {
+    async fn write(&self, saved: &mut Option<SystemTime>) -> Result<()> {
+        let mut config = serde_json::to_value(self)?;
+        if let Ok(disk) = fs::read("config.json").await
+            && let Ok(Value::Object(mut disk)) = serde_json::from_slice(&disk)
+        {
+            for key in ["routes", "bans"] {
+                if let Some(val) = config.get_mut(key) {
+                    disk.insert(key.to_string(), val.take());
+                }
+            }
+            config = Value::Object(disk);
+        }
+        let config = serde_json::to_vec_pretty(&config)?;
+        let mut file = fs::File::create("config.json.tmp").await?;
+        file.write_all(&config).await?;
+        file.sync_all().await?;
+        fs::rename("config.json.tmp", "config.json").await?;
+        *saved = modified().await;
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let mut saved = self.saved.lock().await;
+        self.write(&mut saved).await
+    }
+
+    fn apply(&self, mutation: &Mutation) {
+        match mutation {
+            Mutation::Put { hostname, route } => {
+                self.routes.load().insert(hostname.clone(), route.clone());
+            }
+            Mutation::Delete { hostname } => {
+                self.routes.load().remove(hostname);
+            }
+            Mutation::Ban { cidr, ban } => {
+                self.bans.insert(*cidr, ban.clone());
+            }
+            Mutation::Unban { cidr } => {
+                self.bans.remove(cidr);
+            }
+        }
+    }
+
+    async fn append(&self, journal: &PathBuf, mutation: &Mutation) -> Result<()> {
+        let mut line = serde_json::to_vec(mutation)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal)
+            .await?;
+        file.write_all(&line).await?;
+        file.sync_data().await?;
+        Ok(())
+    }
+
+    pub async fn persist(&self, mutation: Mutation) {
+        let mut saved = self.saved.lock().await;
+        if modified().await != *saved
+            && let Err(error) = self.reload_routes().await
+        {
+            warn!("keeping current routes, error while reloading config.json: {error}");
+        }
+        self.apply(&mutation);
+        if let Some(journal) = &self.journal
+            && let Err(error) = self.append(journal, &mutation).await
+        {
+            warn!("error while appending to {}: {error}", journal.display());
+        }
+        if let Err(error) = self.write(&mut saved).await {
+            warn!("error while saving config.json: {error}");
+        }
+    }
+
+    pub async fn replay(&self) -> Result<()> {
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+        let lines = match fs::read_to_string(journal).await {
+            Ok(lines) => lines,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut replayed = 0;
+        for line in lines.lines() {
+            match serde_json::from_str(line) {
+                Ok(mutation) => self.apply(&mutation),
+                Err(error) => {
+                    warn!(
+                        "stopped replaying {} at invalid entry: {error}",
+                        journal.display()
+                    );
+                    break;
+                }
+            }
+            replayed += 1;
+        }
+        info!("replayed {replayed} mutations from {}", journal.display());
+        self.save().await?;
+        fs::write(journal, "").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{fmt, io::Cursor};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const SEGMENT_BITS: u8 = 0x7F;
+const CONTINUE_BIT: u8 = 0x80;
+
+pub const STATUS: i32 = 1;
+pub const LOGIN: i32 = 2;
+pub const TRANSFER: i32 = 3;
+
+pub const MAX_PACKET_LENGTH: usize = 2097151;
+const MAX_STRING_LENGTH: usize = 32767 * 3;
+const PING_LENGTH: usize = 9;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandshakeConfig {
+    pub timeout_secs: u64,
+    pub max_packet_length: usize,
+    pub max_hostname_length: usize,
+}
+
+impl Default for HandshakeConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 5,
+            max_packet_length: 4096,
+            max_hostname_length: 255,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TooLong(pub &'static str);
+
+impl fmt::Display for TooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} too long", self.0)
+    }
+}
+
+impl std::error::Error for TooLong {}
+
+pub async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i32> {
+    let mut tmp = CONTINUE_BIT;
+    let mut val = 0;
+    let mut pos = 0;
+    while tmp & CONTINUE_BIT == CONTINUE_BIT {
+        if pos >= 35 {
+            bail!("varint too long");
+        }
+        tmp = reader.read_u8().await?;
+        val |= ((tmp & SEGMENT_BITS) as i32) << pos;
+        pos += 7;
+    }
+    Ok(val)
+}
+
+async fn read_length<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max: usize,
+    what: &'static str,
+) -> Result<usize> {
+    let len = read_varint(reader).await?;
+    let Ok(len) = usize::try_from(len) else {
+        bail!("negative {what} length");
+    };
+    if len > max {
+        bail!(TooLong(what));
+    }
+    Ok(len)
+}
+
+pub async fn read_string<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String> {
+    let mut buf = vec![0; read_length(reader, MAX_STRING_LENGTH, "string").await?];
+    reader.read_exact(&mut buf).await?;
+    Ok(String::from_utf8(buf)?)
+}
+
+pub async fn write_varint<W: AsyncWrite + Unpin>(writer: &mut W, val: i32) -> Result<()> {
+    let mut val = val as u32;
+    loop {
+        let tmp = val as u8 & SEGMENT_BITS;
+        val >>= 7;
+        if val > 0 {
+            writer.write_u8(tmp | CONTINUE_BIT).await?;
+        } else {
+            writer.write_u8(tmp).await?;
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub async fn write_string<W: AsyncWrite + Unpin>(writer: &mut W, val: &str) -> Result<()> {
+    write_varint(writer, val.len() as i32).await?;
+    writer.write_all(val.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn read_packet<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max: usize,
+) -> Result<Cursor<Vec<u8>>> {
+    let mut packet = vec![0; read_length(reader, max, "packet").await?];
+    reader.read_exact(&mut packet).await?;
+    Ok(Cursor::new(packet))
+}
+
+pub async fn write_packet<W: AsyncWrite + Unpin>(writer: &mut W, packet: &[u8]) -> Result<()> {
+    let mut buf = Vec::with_capacity(packet.len() + 5);
+    write_varint(&mut buf, packet.len() as i32).await?;
+    buf.extend_from_slice(packet);
+    writer.write_all(&buf).await?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct Handshake {
+    pub protocol: i32,
+    pub hostname: String,
+    pub port: u16,
+    pub next_state: i32,
+}
+
+impl Handshake {
+    pub async fn read(packet: &mut Cursor<Vec<u8>>) -> Result<Option<Self>> {
+        if read_varint(packet).await? != 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            protocol: read_varint(packet).await?,
+            hostname: read_string(packet).await?,
+            port: packet.read_u16().await?,
+            next_state: read_varint(packet).await?,
+        }))
+    }
+
+    pub async fn encode(&self) -> Result<Vec<u8>> {
+        let mut packet = Vec::new();
+        write_varint(&mut packet, 0).await?;
+        write_varint(&mut packet, self.protocol).await?;
+        write_string(&mut packet, &self.hostname).await?;
+        packet.write_u16(self.port).await?;
+        write_varint(&mut packet, self.next_state).await?;
+        Ok(packet)
+    }
+}
+
+async fn skip_bytes(packet: &mut Cursor<Vec<u8>>) -> Result<()> {
+    let remaining = packet.get_ref().len() - packet.position() as usize;
+    let len = read_length(packet, remaining, "byte array").await?;
+    packet.set_position(packet.position() + len as u64);
+    Ok(())
+}
+
+pub fn format_uuid(uuid: u128) -> String {
+    let uuid = format!("{uuid:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &uuid[..8],
+        &uuid[8..12],
+        &uuid[12..16],
+        &uuid[16..20],
+        &uuid[20..]
+    )
+}
+
+pub struct LoginStart {
+    pub name: String,
+    pub uuid: Option<String>,
+}
+
+impl LoginStart {
+    pub async fn read(packet: &mut Cursor<Vec<u8>>, protocol: i32) -> Result<Self> {
+        if read_varint(packet).await? != 0 {
+            bail!("expected login start");
+        }
+        let name = read_string(packet).await?;
+        if matches!(protocol, 759 | 760) && packet.read_u8().await? != 0 {
+            packet.read_i64().await?;
+            skip_bytes(packet).await?;
+            skip_bytes(packet).await?;
+        }
+        let has_uuid = match protocol {
+            ..760 => false,
+            760..764 => packet.read_u8().await? != 0,
+            _ => true,
+        };
+        let uuid = if has_uuid {
+            Some(format_uuid(packet.read_u128().await?))
+        } else {
+            None
+        };
+        Ok(Self { name, uuid })
+    }
+}
+
+pub async fn serve_status<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    status: &str,
+) -> Result<()> {
+    let mut request = read_packet(stream, PING_LENGTH).await?;
+    if read_varint(&mut request).await? != 0 {
+        bail!("expected status request");
+    }
+    let mut response = Vec::new();
+    write_varint(&mut response, 0).await?;
+    write_string(&mut response, status).await?;
+    write_packet(stream, &response).await?;
+    let Ok(mut ping) = read_packet(stream, PING_LENGTH).await else {
+        return Ok(());
+    };
+    if read_varint(&mut ping).await? != 1 {
+        bail!("expected ping request");
+    }
+    let mut pong = Vec::new();
+    write_varint(&mut pong, 1).await?;
+    pong.write_i64(ping.read_i64().await?).await?;
+    write_packet(stream, &pong).await?;
+    Ok(())
+}
+
+pub async fn request_status<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    protocol: i32,
+    hostname: &str,
+    port: u16,
+) -> Result<String> {
+    let handshake = Handshake {
+        protocol,
+        hostname: hostname.to_string(),
+        port,
+        next_state: STATUS,
+    };
+    write_packet(stream, &handshake.encode().await?).await?;
+    write_packet(stream, &[0]).await?;
+    let mut response = read_packet(stream, MAX_PACKET_LENGTH).await?;
+    if read_varint(&mut response).await? != 0 {
+        bail!("expected status response");
+    }
+    read_string(&mut response).await
+}
+
+pub async fn write_disconnect<W: AsyncWrite + Unpin>(writer: &mut W, reason: &str) -> Result<()> {
+    let mut disconnect = Vec::new();
+    write_varint(&mut disconnect, 0).await?;
+    write_string(&mut disconnect, &json!({ "text": reason }).to_string()).await?;
+    write_packet(writer, &disconnect).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: u128 = 0x069a79f444e94726a5befca90e38aaf5;
+
+    async fn parse(protocol: i32, fields: &[u8]) -> Result<LoginStart> {
+        let mut packet = Vec::new();
+        write_varint(&mut packet, 0).await?;
+        write_string(&mut packet, "Notch").await?;
+        packet.extend_from_slice(fields);
+        LoginStart::read(&mut Cursor::new(packet), protocol).await
+    }
+
+    async fn signature(key_len: i32) -> Vec<u8> {
+        let mut data = vec![1];
+        data.extend_from_slice(&1234i64.to_be_bytes());
+        write_varint(&mut data, key_len).await.unwrap();
+        data.extend_from_slice(b"key");
+        write_varint(&mut data, 9).await.unwrap();
+        data.extend_from_slice(b"signature");
+        data
+    }
+
+    fn with_uuid(mut fields: Vec<u8>) -> Vec<u8> {
+        fields.extend_from_slice(&UUID.to_be_bytes());
+        fields
+    }
+
+    fn uuid() -> Option<String> {
+        Some("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string())
+    }
+
+    #[tokio::test]
+    async fn login_start_758() {
+        let login_start = parse(758, &[]).await.unwrap();
+        assert_eq!(login_start.name, "Notch");
+        assert_eq!(login_start.uuid, None);
+    }
+
+    #[tokio::test]
+    async fn login_start_759() {
+        assert_eq!(parse(759, &[0]).await.unwrap().uuid, None);
+        let login_start = parse(759, &signature(3).await).await.unwrap();
+        assert_eq!(login_start.name, "Notch");
+        assert_eq!(login_start.uuid, None);
+    }
+
+    #[tokio::test]
+    async fn login_start_760() {
+        let mut fields = signature(3).await;
+        fields.push(1);
+        let fields = with_uuid(fields);
+        assert_eq!(parse(760, &fields).await.unwrap().uuid, uuid());
+        let fields = with_uuid(vec![0, 1]);
+        assert_eq!(parse(760, &fields).await.unwrap().uuid, uuid());
+        assert_eq!(parse(760, &[0, 0]).await.unwrap().uuid, None);
+    }
+
+    #[tokio::test]
+    async fn login_start_763() {
+        let fields = with_uuid(vec![1]);
+        assert_eq!(parse(763, &fields).await.unwrap().uuid, uuid());
+        assert_eq!(parse(763, &[0]).await.unwrap().uuid, None);
+    }
+
+    #[tokio::test]
+    async fn login_start_764() {
+        let login_start = parse(764, &with_uuid(Vec::new())).await.unwrap();
+        assert_eq!(login_start.name, "Notch");
+        assert_eq!(login_start.uuid, uuid());
+        assert!(parse(764, &[0; 8]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn login_start_invalid_signature() {
+        assert!(parse(759, &signature(100).await).await.is_err());
+        assert!(parse(759, &signature(-1).await).await.is_err());
+    }
+}
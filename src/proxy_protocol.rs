@@ -0,0 +1,209 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const V1_MAX_LENGTH: usize = 107;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyProtocol {
+    V1,
+    V2,
+}
+
+fn canonical(src: SocketAddr, dst: SocketAddr) -> (SocketAddr, SocketAddr) {
+    let src = SocketAddr::new(src.ip().to_canonical(), src.port());
+    let dst = SocketAddr::new(dst.ip().to_canonical(), dst.port());
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => (src, dst),
+        _ => (mapped(src), mapped(dst)),
+    }
+}
+
+fn mapped(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), addr.port()),
+        IpAddr::V6(_) => addr,
+    }
+}
+
+impl ProxyProtocol {
+    pub fn encode(self, src: SocketAddr, dst: SocketAddr) -> Vec<u8> {
+        let (src, dst) = canonical(src, dst);
+        match self {
+            ProxyProtocol::V1 => {
+                let family = if src.is_ipv4() { "TCP4" } else { "TCP6" };
+                format!(
+                    "PROXY {family} {} {} {} {}\r\n",
+                    src.ip(),
+                    dst.ip(),
+                    src.port(),
+                    dst.port()
+                )
+                .into_bytes()
+            }
+            ProxyProtocol::V2 => {
+                let mut header = SIGNATURE.to_vec();
+                header.push(0x21);
+                match (src.ip(), dst.ip()) {
+                    (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+                        header.push(0x11);
+                        header.extend_from_slice(&12u16.to_be_bytes());
+                        header.extend_from_slice(&src_ip.octets());
+                        header.extend_from_slice(&dst_ip.octets());
+                    }
+                    (IpAddr::V6(src_ip), IpAddr::V6(dst_ip)) => {
+                        header.push(0x21);
+                        header.extend_from_slice(&36u16.to_be_bytes());
+                        header.extend_from_slice(&src_ip.octets());
+                        header.extend_from_slice(&dst_ip.octets());
+                    }
+                    _ => unreachable!(),
+                }
+                header.extend_from_slice(&src.port().to_be_bytes());
+                header.extend_from_slice(&dst.port().to_be_bytes());
+                header
+            }
+        }
+    }
+}
+
+pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<SocketAddr>> {
+    match reader.read_u8().await? {
+        b'P' => read_v1(reader).await,
+        b'\r' => read_v2(reader).await,
+        _ => bail!("missing proxy protocol header"),
+    }
+}
+
+async fn read_v1<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<SocketAddr>> {
+    let mut line = vec![b'P'];
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            bail!("proxy protocol v1 header too long");
+        }
+        line.push(reader.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])?;
+    let mut fields = line.split(' ');
+    if fields.next() != Some("PROXY") {
+        bail!("invalid proxy protocol v1 header");
+    }
+    match fields.next() {
+        Some("TCP4" | "TCP6") => {}
+        Some("UNKNOWN") => return Ok(None),
+        _ => bail!("invalid proxy protocol v1 family"),
+    }
+    let ip = fields.next().context("missing proxy protocol v1 source")?;
+    let port = fields.nth(1).context("missing proxy protocol v1 port")?;
+    Ok(Some(SocketAddr::new(ip.parse()?, port.parse()?)))
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<SocketAddr>> {
+    let mut header = [0; 16];
+    header[0] = b'\r';
+    reader.read_exact(&mut header[1..]).await?;
+    if header[..12] != SIGNATURE || header[12] >> 4 != 2 {
+        bail!("invalid proxy protocol v2 header");
+    }
+    let mut payload = vec![0; u16::from_be_bytes([header[14], header[15]]) as usize];
+    reader.read_exact(&mut payload).await?;
+    if header[12] & 0x0F == 0 {
+        return Ok(None);
+    }
+    match header[13] >> 4 {
+        1 if payload.len() >= 12 => {
+            let ip: [u8; 4] = payload[..4].try_into()?;
+            let port = u16::from_be_bytes([payload[8], payload[9]]);
+            Ok(Some(SocketAddr::from((ip, port))))
+        }
+        2 if payload.len() >= 36 => {
+            let ip: [u8; 16] = payload[..16].try_into()?;
+            let port = u16::from_be_bytes([payload[32], payload[33]]);
+            Ok(Some(SocketAddr::from((ip, port))))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn round_trip(version: ProxyProtocol, src: &str, dst: &str) -> Option<SocketAddr> {
+        let mut header = version.encode(src.parse().unwrap(), dst.parse().unwrap());
+        header.extend_from_slice(b"rest");
+        let mut reader = header.as_slice();
+        let addr = read(&mut reader).await.unwrap();
+        assert_eq!(reader, b"rest");
+        addr
+    }
+
+    #[tokio::test]
+    async fn round_trips() {
+        for version in [ProxyProtocol::V1, ProxyProtocol::V2] {
+            assert_eq!(
+                round_trip(version, "1.2.3.4:5000", "10.0.0.1:25565").await,
+                Some("1.2.3.4:5000".parse().unwrap())
+            );
+            assert_eq!(
+                round_trip(version, "[2001:db8::1]:5000", "[2001:db8::2]:25565").await,
+                Some("[2001:db8::1]:5000".parse().unwrap())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn mixed_families() {
+        for version in [ProxyProtocol::V1, ProxyProtocol::V2] {
+            let addr = round_trip(version, "1.2.3.4:5000", "[2001:db8::2]:25565").await;
+            assert_eq!(addr, Some("[::ffff:1.2.3.4]:5000".parse().unwrap()));
+            let addr = round_trip(version, "[::ffff:1.2.3.4]:5000", "10.0.0.1:25565").await;
+            assert_eq!(addr, Some("1.2.3.4:5000".parse().unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn v1_unknown() {
+        let mut reader = b"PROXY UNKNOWN\r\nrest".as_slice();
+        assert_eq!(read(&mut reader).await.unwrap(), None);
+        assert_eq!(reader, b"rest");
+    }
+
+    #[tokio::test]
+    async fn v2_local() {
+        let mut header = SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        header.extend_from_slice(b"rest");
+        let mut reader = header.as_slice();
+        assert_eq!(read(&mut reader).await.unwrap(), None);
+        assert_eq!(reader, b"rest");
+    }
+
+    #[tokio::test]
+    async fn v2_unspecified_family() {
+        let mut header = SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x00, 0x00, 0x02, 0xAA, 0xBB]);
+        header.extend_from_slice(b"rest");
+        let mut reader = header.as_slice();
+        assert_eq!(read(&mut reader).await.unwrap(), None);
+        assert_eq!(reader, b"rest");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid() {
+        assert!(read(&mut b"\x10\x00".as_slice()).await.is_err());
+        assert!(
+            read(&mut b"PROXY TCP5 1.2.3.4\r\n".as_slice())
+                .await
+                .is_err()
+        );
+        let long = [b"PROXY TCP4 ".as_slice(), &[b'1'; 200]].concat();
+        assert!(read(&mut long.as_slice()).await.is_err());
+        let mut header = SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x11, 0x11, 0x00, 0x00]);
+        assert!(read(&mut header.as_slice()).await.is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{Server, cidr::Cidr, metrics::inc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time;
+use tracing::debug;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TokenBucketConfig {
+    pub burst: f64,
+    pub per_sec: f64,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_ip: Option<TokenBucketConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_prefix: Option<TokenBucketConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections_per_ip: Option<usize>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucketConfig {
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_sec).min(self.burst)
+    }
+
+    fn take<K: Eq + std::hash::Hash>(&self, buckets: &DashMap<K, Bucket>, key: K) -> bool {
+        let now = Instant::now();
+        let mut bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            last: now,
+        });
+        bucket.tokens = self.refill(&bucket, now);
+        bucket.last = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    ips: DashMap<IpAddr, Bucket>,
+    prefixes: DashMap<Cidr, Bucket>,
+    connections: Arc<DashMap<IpAddr, usize>>,
+}
+
+pub struct Permit {
+    connections: Arc<DashMap<IpAddr, usize>>,
+    ip: IpAddr,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.connections.remove_if_mut(&self.ip, |_, connections| {
+            *connections -= 1;
+            *connections == 0
+        });
+    }
+}
+
+fn prefix(ip: IpAddr) -> Cidr {
+    match ip {
+        IpAddr::V4(_) => Cidr::new(ip, 24),
+        IpAddr::V6(_) => Cidr::new(ip, 64),
+    }
+}
+
+impl Server {
+    pub fn accept(&self, addr: SocketAddr) -> Option<Permit> {
+        let ip = addr.ip().to_canonical();
+        if !self.admits(ip) {
+            debug!("rejected connection from {addr}");
+            return None;
+        }
+        let limiter = &self.rate_limiter;
+        if let Some(limit) = &self.rate_limit.per_ip
+            && !limit.take(&limiter.ips, ip)
+        {
+            debug!("rate limited connection from {addr}");
+            inc(&self.metrics.rate_limited_ip);
+            return None;
+        }
+        if let Some(limit) = &self.rate_limit.per_prefix
+            && !limit.take(&limiter.prefixes, prefix(ip))
+        {
+            debug!("rate limited connection from {addr}");
+            inc(&self.metrics.rate_limited_prefix);
+            return None;
+        }
+        let mut connections = limiter.connections.entry(ip).or_insert(0);
+        if self
+            .rate_limit
+            .max_connections_per_ip
+            .is_some_and(|max| *connections >= max)
+        {
+            debug!("too many connections from {addr}");
+            inc(&self.metrics.connection_limited);
+            return None;
+        }
+        *connections += 1;
+        Some(Permit {
+            connections: limiter.connections.clone(),
+            ip,
+        })
+    }
+
+    pub async fn prune_rate_limits(self: Arc<Self>) {
+        let mut interval = time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            let limiter = &self.rate_limiter;
+            if let Some(limit) = &self.rate_limit.per_ip {
+                limiter
+                    .ips
+                    .retain(|_, bucket| limit.refill(bucket, now) < limit.burst);
+            }
+            if let Some(limit) = &self.rate_limit.per_prefix {
+                limiter
+                    .prefixes
+                    .retain(|_, bucket| limit.refill(bucket, now) < limit.burst);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Server, persist::modified};
+use anyhow::Result;
+use std::{sync::Arc, time::Duration};
+use tokio::{fs, join, time};
+use tracing::{info, warn};
+
+impl Server {
+    pub async fn reload_routes(&self) -> Result<()> {
+        let config = fs::read_to_string("config.json").await?;
+        let server = serde_json::from_str::<Server>(&config)?;
+        let routes = server.routes.into_inner();
+        info!("reloaded {} routes from config.json", routes.len());
+        self.routes.store(routes);
+        Ok(())
+    }
+
+    async fn try_reload(&self) {
+        let _saved = self.saved.lock().await;
+        if let Err(error) = self.reload_routes().await {
+            warn!("keeping current config, error while reloading config.json: {error}");
+        }
+    }
+
+    async fn watch_file(&self) {
+        let mut last = modified().await;
+        let mut interval = time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let current = modified().await;
+            let saved = *self.saved.lock().await;
+            if current.is_some() && current != last && current != saved {
+                info!("config.json changed");
+                self.try_reload().await;
+            }
+            last = current;
+        }
+    }
+
+    #[cfg(unix)]
+    async fn watch_hangup(&self) {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(error) => {
+                warn!("failed to listen for SIGHUP: {error}");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            info!("received SIGHUP");
+            self.try_reload().await;
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn watch_hangup(&self) {}
+
+    pub async fn watch(self: Arc<Self>) {
+        join!(self.watch_file(), self.watch_hangup());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{
+    backend::Policy,
+    cidr::Cidr,
+    protocol::LoginStart,
+    proxy_protocol::ProxyProtocol,
+    status::{StatusConfig, StatusOverride},
+    version::ProtocolRange,
+};
+use anyhow::{Result, bail};
+use dashmap::DashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, atomic::AtomicUsize},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
+pub struct Route {
+    #[serde(alias = "origin", deserialize_with = "one_or_many")]
+    pub origins: Vec<SocketAddr>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub policy: Policy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_override: Option<StatusOverride>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protocol_origins: Vec<ProtocolOrigins>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocols: Option<ProtocolRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_protocol: Option<ProxyProtocol>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bungee_forwarding: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_players: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_players: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_ips: Vec<Cidr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_ips: Vec<Cidr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_session_secs: Option<u64>,
+    #[serde(skip)]
+    pub next: Arc<AtomicUsize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProtocolOrigins {
+    #[serde(flatten)]
+    pub protocols: ProtocolRange,
+    #[serde(alias = "origin", deserialize_with = "one_or_many")]
+    pub origins: Vec<SocketAddr>,
+}
+
+fn is_default<T: Default + PartialEq>(val: &T) -> bool {
+    *val == T::default()
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<SocketAddr>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        One(SocketAddr),
+        Many(Vec<SocketAddr>),
+    }
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::One(origin) => vec![origin],
+        Repr::Many(origins) => origins,
+    })
+}
+
+impl Route {
+    pub fn new(origin: SocketAddr) -> Self {
+        Self {
+            origins: vec![origin],
+            policy: Policy::default(),
+            status: None,
+            status_override: None,
+            protocol_origins: Vec::new(),
+            protocols: None,
+            group: None,
+            aggregate: None,
+            proxy_protocol: None,
+            bungee_forwarding: false,
+            allowed_players: Vec::new(),
+            denied_players: Vec::new(),
+            allowed_ips: Vec::new(),
+            denied_ips: Vec::new(),
+            max_session_secs: None,
+            next: Arc::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.origins.is_empty() {
+            bail!("route has no origins");
+        }
+        for entry in &self.protocol_origins {
+            if entry.origins.is_empty() {
+                bail!(
+                    "protocol origins for {} have no origins",
+                    entry.protocols.describe()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn select(&self, protocol: i32) -> Option<usize> {
+        self.protocol_origins
+            .iter()
+            .position(|entry| entry.protocols.contains(protocol))
+    }
+
+    pub fn origins(&self, protocol: i32) -> &[SocketAddr] {
+        match self.select(protocol) {
+            Some(index) => &self.protocol_origins[index].origins,
+            None => &self.origins,
+        }
+    }
+
+    pub fn all_origins(&self) -> impl Iterator<Item = SocketAddr> {
+        self.protocol_origins
+            .iter()
+            .flat_map(|entry| &entry.origins)
+            .chain(&self.origins)
+            .copied()
+    }
+
+    pub fn admits(&self, login_start: &LoginStart) -> bool {
+        let uuid = login_start.uuid.as_ref().map(|uuid| uuid.replace('-', ""));
+        let named = |player: &String| player.eq_ignore_ascii_case(&login_start.name);
+        let denied = |player: &String| {
+            named(player)
+                || uuid
+                    .as_ref()
+                    .is_some_and(|uuid| player.replace('-', "").eq_ignore_ascii_case(uuid))
+        };
+        !self.denied_players.iter().any(denied)
+            && (self.allowed_players.is_empty() || self.allowed_players.iter().any(named))
+    }
+
+    pub fn players(&mut self, list: &str) -> Option<&mut Vec<String>> {
+        match list {
+            "allowed_players" => Some(&mut self.allowed_players),
+            "denied_players" => Some(&mut self.denied_players),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Route {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Route::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Route {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Remote(#[serde(with = "Route")] Route);
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Origin(SocketAddr),
+            Route(Box<Remote>),
+        }
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Origin(origin) => Route::new(origin),
+            Repr::Route(route) => route.0,
+        })
+    }
+}
+
+pub fn normalize(hostname: &str) -> String {
+    let hostname = hostname
+        .split_once('\0')
+        .map_or(hostname, |(hostname, _)| hostname);
+    hostname
+        .strip_suffix('.')
+        .unwrap_or(hostname)
+        .to_ascii_lowercase()
+}
+
+pub fn matches(pattern: &str, hostname: &str) -> bool {
+    match pattern.strip_prefix('*') {
+        Some("") => true,
+        Some(suffix) if suffix.starts_with('.') => hostname.ends_with(suffix),
+        _ => pattern == hostname,
+    }
+}
+
+pub fn lookup(
+    routes: &DashMap<String, Arc<Route>>,
+    hostname: &str,
+) -> Option<(String, Arc<Route>)> {
+    let entry = |route: dashmap::mapref::one::Ref<String, Arc<Route>>| {
+        (route.key().clone(), route.value().clone())
+    };
+    if let Some(route) = routes.get(hostname) {
+        return Some(entry(route));
+    }
+    let mut pattern = String::with_capacity(hostname.len() + 1);
+    let mut suffix = hostname;
+    while let Some(index) = suffix.find('.') {
+        suffix = &suffix[index + 1..];
+        pattern.clear();
+        pattern.push_str("*.");
+        pattern.push_str(suffix);
+        if let Some(route) = routes.get(pattern.as_str()) {
+            return Some(entry(route));
+        }
+    }
+    routes.get("*").map(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(patterns: &[(&str, u16)]) -> DashMap<String, Arc<Route>> {
+        patterns
+            .iter()
+            .map(|(pattern, port)| {
+                let origin = SocketAddr::from(([127, 0, 0, 1], *port));
+                (pattern.to_string(), Arc::new(Route::new(origin)))
+            })
+            .collect()
+    }
+
+    fn port(routes: &DashMap<String, Arc<Route>>, hostname: &str) -> Option<(String, u16)> {
+        lookup(routes, hostname).map(|(pattern, route)| (pattern, route.origins[0].port()))
+    }
+
+    #[test]
+    fn lookup_prefers_most_specific() {
+        let routes = routes(&[
+            ("play.example.com", 1),
+            ("*.play.example.com", 2),
+            ("*.example.com", 3),
+            ("*", 4),
+        ]);
+        assert_eq!(
+            port(&routes, "play.example.com"),
+            Some(("play.example.com".to_string(), 1))
+        );
+        assert_eq!(
+            port(&routes, "eu.play.example.com"),
+            Some(("*.play.example.com".to_string(), 2))
+        );
+        assert_eq!(
+            port(&routes, "a.b.example.com"),
+            Some(("*.example.com".to_string(), 3))
+        );
+        assert_eq!(port(&routes, "example.com"), Some(("*".to_string(), 4)));
+        assert_eq!(port(&routes, "other.net"), Some(("*".to_string(), 4)));
+    }
+
+    #[test]
+    fn lookup_without_catch_all() {
+        let routes = routes(&[("*.example.com", 1)]);
+        assert_eq!(port(&routes, "example.com"), None);
+        assert_eq!(port(&routes, "badexample.com"), None);
+        assert_eq!(port(&routes, ""), None);
+    }
+
+    #[test]
+    fn normalize_strips_markers() {
+        assert_eq!(normalize("Play.Example.com"), "play.example.com");
+        assert_eq!(normalize("play.example.com."), "play.example.com");
+        assert_eq!(normalize("play.example.com\0FML\0"), "play.example.com");
+        assert_eq!(normalize("play.example.com\0FML3\0"), "play.example.com");
+        assert_eq!(normalize("Play.Example.com.\0FML2\0"), "play.example.com");
+        assert_eq!(
+            normalize("play.example.com\x00127.0.0.1\x00uuid"),
+            "play.example.com"
+        );
+    }
+
+    #[test]
+    fn matches_patterns() {
+        assert!(matches("*", "anything"));
+        assert!(matches("*.example.com", "a.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(matches("example.com", "example.com"));
+        assert!(!matches("example.com", "a.example.com"));
+    }
+
+    #[test]
+    fn validate_requires_origins() {
+        let route = |json| serde_json::from_str::<Route>(json).unwrap().validate();
+        assert!(route(r#"{"origins": "127.0.0.1:25565"}"#).is_ok());
+        assert!(route(r#"{"origins": []}"#).is_err());
+        assert!(
+            route(r#"{"origins": [], "protocol_origins": [{"origins": "127.0.0.1:1"}]}"#).is_err()
+        );
+        assert!(
+            route(
+                r#"{"origins": "127.0.0.1:1", "protocol_origins": [{"max": 340, "origins": []}]}"#
+            )
+            .is_err()
+        );
+    }
+}
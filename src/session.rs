@@ -0,0 +1,78 @@
+use crate::{now, protocol::LoginStart};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::sync::Notify;
+
+#[derive(Serialize)]
+pub struct Session {
+    pub id: u64,
+    pub addr: SocketAddr,
+    pub hostname: String,
+    pub protocol: i32,
+    pub backend: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    pub started_at: u64,
+    pub bytes_in: AtomicU64,
+    pub bytes_out: AtomicU64,
+    #[serde(skip)]
+    pub kick: Notify,
+}
+
+#[derive(Default)]
+pub struct Sessions {
+    next: AtomicU64,
+    pub sessions: DashMap<u64, Arc<Session>>,
+}
+
+impl Sessions {
+    pub fn open(
+        &self,
+        addr: SocketAddr,
+        hostname: String,
+        protocol: i32,
+        backend: SocketAddr,
+        login_start: Option<&LoginStart>,
+    ) -> Open<'_> {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        let started_at = now();
+        let session = Arc::new(Session {
+            id,
+            addr,
+            hostname,
+            protocol,
+            backend,
+            username: login_start.map(|login_start| login_start.name.clone()),
+            uuid: login_start.and_then(|login_start| login_start.uuid.clone()),
+            started_at,
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            kick: Notify::new(),
+        });
+        self.sessions.insert(id, session.clone());
+        Open {
+            sessions: self,
+            session,
+        }
+    }
+}
+
+pub struct Open<'a> {
+    sessions: &'a Sessions,
+    pub session: Arc<Session>,
+}
+
+impl Drop for Open<'_> {
+    fn drop(&mut self) {
+        self.sessions.sessions.remove(&self.session.id);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::Server;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{select, signal, sync::Notify, time};
+use tracing::{info, warn};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    pub drain_timeout_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: 30,
+            message: None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Tracker {
+    active: AtomicUsize,
+    idle: Notify,
+}
+
+impl Tracker {
+    pub fn track(self: &Arc<Self>) -> Tracked {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Tracked(self.clone())
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    async fn idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.active() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+pub struct Tracked(Arc<Tracker>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        if self.0.active.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+    signal(SignalKind::terminate())?.recv().await;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn terminate() -> Result<()> {
+    std::future::pending().await
+}
+
+async fn stop() -> Result<()> {
+    select! {
+        result = signal::ctrl_c() => result?,
+        result = terminate() => result?,
+    }
+    Ok(())
+}
+
+impl Server {
+    pub async fn shutdown(self: Arc<Self>) -> Result<()> {
+        stop().await?;
+        info!("gracefully shutting down");
+        self.draining.store(true, Ordering::Relaxed);
+        self.save().await?;
+        let active = self.connections.active();
+        if active == 0 {
+            return Ok(());
+        }
+        info!("waiting for {active} connections to close");
+        let timeout = Duration::from_secs(self.shutdown.drain_timeout_secs);
+        select! {
+            _ = self.connections.idle() => info!("all connections closed"),
+            _ = time::sleep(timeout) => {
+                warn!("closing {} connections after drain timeout", self.connections.active());
+            }
+            result = stop() => {
+                result?;
+                warn!("closing {} connections immediately", self.connections.active());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{
+    Server,
+    metrics::RouteMetrics,
+    protocol::{Handshake, request_status},
+    route::Route,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::HashSet, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::AsyncWriteExt,
+    task::JoinSet,
+    time::{self, Instant},
+};
+use tracing::warn;
+
+const SAMPLE_SIZE: usize = 12;
+
+pub type StatusKey = (String, Option<usize>, i32);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Status {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Version>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub players: Option<Players>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub description: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Version {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Players {
+    pub max: i32,
+    pub online: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sample: Vec<Player>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    pub motd: String,
+    pub version: String,
+    pub online: i32,
+    pub max: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            motd: "Server is not available".to_string(),
+            version: "Hopper".to_string(),
+            online: 0,
+            max: 0,
+            favicon: None,
+        }
+    }
+}
+
+impl StatusConfig {
+    pub fn status(&self, protocol: i32) -> Status {
+        Status {
+            version: Some(Version {
+                name: self.version.clone(),
+                protocol,
+            }),
+            players: Some(Players {
+                max: self.max,
+                online: self.online,
+                sample: Vec::new(),
+            }),
+            description: Value::String(self.motd.clone()),
+            favicon: self.favicon.clone(),
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub motd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i32>,
+}
+
+impl StatusOverride {
+    pub fn apply(&self, status: &mut Status) {
+        if let Some(motd) = &self.motd {
+            status.description = Value::String(motd.clone());
+        }
+        if let Some(favicon) = &self.favicon {
+            status.favicon = Some(favicon.clone());
+        }
+        if let Some(max) = self.max {
+            status.players.get_or_insert_default().max = max;
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusCacheConfig {
+    pub ttl_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for StatusCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 5,
+            timeout_secs: 3,
+        }
+    }
+}
+
+impl Server {
+    async fn fetch_status(
+        &self,
+        route: &Route,
+        handshake: &Handshake,
+        metrics: &RouteMetrics,
+        addr: SocketAddr,
+        local: SocketAddr,
+    ) -> Result<Option<Status>> {
+        let fetch = async {
+            let Some((_, _connection, mut origin)) = self
+                .connect(route, handshake.protocol, &handshake.hostname, metrics)
+                .await
+            else {
+                return Ok(None);
+            };
+            if let Some(proxy_protocol) = route.proxy_protocol {
+                origin
+                    .write_all(&proxy_protocol.encode(addr, local))
+                    .await?;
+            }
+            let Handshake {
+                protocol,
+                hostname,
+                port,
+                ..
+            } = handshake;
+            let status = request_status(&mut origin, *protocol, hostname, *port).await?;
+            Ok(Some(serde_json::from_str(&status)?))
+        };
+        let timeout = Duration::from_secs(self.status_cache.timeout_secs);
+        let Ok(status) = time::timeout(timeout, fetch).await else {
+            warn!("status request for {} timed out", handshake.hostname);
+            return Ok(None);
+        };
+        status
+    }
+
+    async fn cached_status(
+        &self,
+        pattern: &str,
+        route: &Route,
+        handshake: &Handshake,
+        metrics: &RouteMetrics,
+        addr: SocketAddr,
+        local: SocketAddr,
+    ) -> Option<Status> {
+        let ttl = Duration::from_secs(self.status_cache.ttl_secs);
+        let protocol = handshake.protocol;
+        let key = (pattern.to_string(), route.select(protocol), protocol);
+        let cached = self
+            .statuses
+            .get(&key)
+            .filter(|cached| cached.0.elapsed() < ttl)
+            .map(|cached| cached.1.clone());
+        if cached.is_some() {
+            return cached;
+        }
+        match self
+            .fetch_status(route, handshake, metrics, addr, local)
+            .await
+        {
+            Ok(Some(status)) => {
+                self.statuses.retain(|_, cached| cached.0.elapsed() < ttl);
+                self.statuses.insert(key, (Instant::now(), status.clone()));
+                Some(status)
+            }
+            Ok(None) => None,
+            Err(error) => {
+                warn!("failed to request status for {pattern}: {error}");
+                None
+            }
+        }
+    }
+
+    pub async fn route_status(
+        &self,
+        pattern: &str,
+        route: &Route,
+        handshake: &Handshake,
+        metrics: &RouteMetrics,
+        addr: SocketAddr,
+        local: SocketAddr,
+    ) -> Option<Status> {
+        let mut status = self
+            .cached_status(pattern, route, handshake, metrics, addr, local)
+            .await?;
+        if let Some(status_override) = &route.status_override {
+            status_override.apply(&mut status);
+        }
+        Some(status)
+    }
+
+    pub async fn aggregate_status(
+        self: &Arc<Self>,
+        pattern: &str,
+        route: &Route,
+        handshake: &Handshake,
+        metrics: &RouteMetrics,
+        addr: SocketAddr,
+        local: SocketAddr,
+    ) -> Status {
+        let group = route.aggregate.as_deref().unwrap_or("*");
+        let protocol = handshake.protocol;
+        let mut routes = self
+            .routes
+            .load()
+            .iter()
+            .filter(|member| {
+                member.key() != pattern
+                    && member.aggregate.is_none()
+                    && (group == "*" || member.group.as_deref() == Some(group))
+            })
+            .map(|member| (member.key().clone(), member.value().clone()))
+            .collect::<Vec<_>>();
+        routes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut backends = route
+            .origins(protocol)
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        let mut members = JoinSet::new();
+        for (pattern, route) in routes {
+            let origins = route.origins(protocol);
+            if origins.iter().any(|origin| backends.contains(origin)) {
+                continue;
+            }
+            backends.extend(origins.iter().copied());
+            let server = self.clone();
+            let handshake = handshake.clone();
+            members.spawn(async move {
+                let metrics = server.metrics.route(&pattern);
+                server
+                    .route_status(&pattern, &route, &handshake, &metrics, addr, local)
+                    .await
+            });
+        }
+        let own = self
+            .cached_status(pattern, route, handshake, metrics, addr, local)
+            .await;
+        let mut status = own.unwrap_or_else(|| {
+            route
+                .status
+                .as_ref()
+                .unwrap_or(&self.status)
+                .status(handshake.protocol)
+        });
+        let players = status.players.get_or_insert_default();
+        let mut ids = players
+            .sample
+            .iter()
+            .map(|player| player.id.clone())
+            .collect::<HashSet<_>>();
+        while let Some(member) = members.join_next().await {
+            let Ok(Some(Status {
+                players: Some(member),
+                ..
+            })) = member
+            else {
+                continue;
+            };
+            players.online = players.online.saturating_add(member.online);
+            players.max = players.max.saturating_add(member.max);
+            for player in member.sample {
+                if ids.insert(player.id.clone()) {
+                    players.sample.push(player);
+                }
+            }
+        }
+        players.sample.truncate(SAMPLE_SIZE);
+        if let Some(status_override) = &route.status_override {
+            status_override.apply(&mut status);
+        }
+        status
+    }
+}
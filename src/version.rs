@@ -0,0 +1,189 @@
+use crate::status::Status;
+use serde::{Deserialize, Serialize};
+
+const RELEASES: &[(i32, &str, &str)] = &[
+    (4, "1.7.2", "1.7.5"),
+    (5, "1.7.6", "1.7.10"),
+    (47, "1.8", "1.8.9"),
+    (107, "1.9", "1.9"),
+    (108, "1.9.1", "1.9.1"),
+    (109, "1.9.2", "1.9.2"),
+    (110, "1.9.3", "1.9.4"),
+    (210, "1.10", "1.10.2"),
+    (315, "1.11", "1.11"),
+    (316, "1.11.1", "1.11.2"),
+    (335, "1.12", "1.12"),
+    (338, "1.12.1", "1.12.1"),
+    (340, "1.12.2", "1.12.2"),
+    (393, "1.13", "1.13"),
+    (401, "1.13.1", "1.13.1"),
+    (404, "1.13.2", "1.13.2"),
+    (477, "1.14", "1.14"),
+    (480, "1.14.1", "1.14.1"),
+    (485, "1.14.2", "1.14.2"),
+    (490, "1.14.3", "1.14.3"),
+    (498, "1.14.4", "1.14.4"),
+    (573, "1.15", "1.15"),
+    (575, "1.15.1", "1.15.1"),
+    (578, "1.15.2", "1.15.2"),
+    (735, "1.16", "1.16"),
+    (736, "1.16.1", "1.16.1"),
+    (751, "1.16.2", "1.16.2"),
+    (753, "1.16.3", "1.16.3"),
+    (754, "1.16.4", "1.16.5"),
+    (755, "1.17", "1.17"),
+    (756, "1.17.1", "1.17.1"),
+    (757, "1.18", "1.18.1"),
+    (758, "1.18.2", "1.18.2"),
+    (759, "1.19", "1.19"),
+    (760, "1.19.1", "1.19.2"),
+    (761, "1.19.3", "1.19.3"),
+    (762, "1.19.4", "1.19.4"),
+    (763, "1.20", "1.20.1"),
+    (764, "1.20.2", "1.20.2"),
+    (765, "1.20.3", "1.20.4"),
+    (766, "1.20.5", "1.20.6"),
+    (767, "1.21", "1.21.1"),
+    (768, "1.21.2", "1.21.3"),
+    (769, "1.21.4", "1.21.4"),
+    (770, "1.21.5", "1.21.5"),
+    (771, "1.21.6", "1.21.6"),
+    (772, "1.21.7", "1.21.8"),
+    (773, "1.21.9", "1.21.10"),
+];
+
+fn oldest(protocol: i32) -> String {
+    RELEASES
+        .iter()
+        .find(|(release, _, _)| *release >= protocol)
+        .map_or(format!("protocol {protocol}"), |(_, oldest, _)| {
+            oldest.to_string()
+        })
+}
+
+fn newest(protocol: i32) -> String {
+    RELEASES
+        .iter()
+        .rev()
+        .find(|(release, _, _)| *release <= protocol)
+        .map_or(format!("protocol {protocol}"), |(_, _, newest)| {
+            newest.to_string()
+        })
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolRange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i32>,
+}
+
+impl ProtocolRange {
+    pub fn contains(&self, protocol: i32) -> bool {
+        self.min.is_none_or(|min| protocol >= min) && self.max.is_none_or(|max| protocol <= max)
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => format!("{}–{}", oldest(min), newest(max)),
+            (Some(min), None) => format!("{} or newer", oldest(min)),
+            (None, Some(max)) => format!("{} or older", newest(max)),
+            (None, None) => "any version".to_string(),
+        }
+    }
+
+    pub fn target(&self, protocol: i32) -> Option<(&'static str, String)> {
+        match (self.min, self.max) {
+            (Some(min), _) if protocol < min => Some(("upgrade", oldest(min))),
+            (_, Some(max)) if protocol > max => Some(("downgrade", newest(max))),
+            _ => None,
+        }
+    }
+
+    pub fn gate(&self, status: &mut Status, protocol: i32) {
+        match self.target(protocol) {
+            Some(_) => {
+                let version = status.version.get_or_insert_default();
+                version.name = format!("Requires {}", self.describe());
+                version.protocol = self
+                    .min
+                    .filter(|min| protocol < *min)
+                    .or(self.max)
+                    .unwrap_or(-1);
+            }
+            None => {
+                if let Some(version) = &mut status.version {
+                    version.protocol = protocol;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StatusConfig;
+
+    fn range(min: Option<i32>, max: Option<i32>) -> ProtocolRange {
+        ProtocolRange { min, max }
+    }
+
+    #[test]
+    fn describe() {
+        assert_eq!(range(Some(765), Some(767)).describe(), "1.20.3–1.21.1");
+        assert_eq!(range(Some(764), None).describe(), "1.20.2 or newer");
+        assert_eq!(range(None, Some(763)).describe(), "1.20.1 or older");
+        assert_eq!(range(Some(5), Some(47)).describe(), "1.7.6–1.8.9");
+        assert_eq!(range(None, None).describe(), "any version");
+    }
+
+    #[test]
+    fn describe_unknown_protocols() {
+        assert_eq!(range(Some(100), Some(200)).describe(), "1.9–1.9.4");
+        assert_eq!(range(Some(1), None).describe(), "1.7.2 or newer");
+        assert_eq!(range(Some(9999), None).describe(), "protocol 9999 or newer");
+        assert_eq!(range(None, Some(1)).describe(), "protocol 1 or older");
+    }
+
+    #[test]
+    fn target() {
+        let range = range(Some(765), Some(767));
+        assert_eq!(range.target(764), Some(("upgrade", "1.20.3".to_string())));
+        assert_eq!(range.target(768), Some(("downgrade", "1.21.1".to_string())));
+        assert_eq!(range.target(765), None);
+        assert_eq!(range.target(767), None);
+        assert!(range.contains(766));
+        assert!(!range.contains(768));
+        assert_eq!(ProtocolRange::default().target(5), None);
+    }
+
+    fn version(status: &Status) -> (&str, i32) {
+        let version = status.version.as_ref().unwrap();
+        (&version.name, version.protocol)
+    }
+
+    #[test]
+    fn gate() {
+        let range = range(Some(765), Some(767));
+        let mut status = StatusConfig::default().status(0);
+        range.gate(&mut status, 766);
+        assert_eq!(version(&status), ("Hopper", 766));
+        range.gate(&mut status, 764);
+        assert_eq!(version(&status), ("Requires 1.20.3–1.21.1", 765));
+        range.gate(&mut status, 770);
+        assert_eq!(version(&status).1, 767);
+    }
+
+    #[test]
+    fn gate_without_version() {
+        let range = range(Some(765), Some(767));
+        let mut status = serde_json::from_str::<Status>("{}").unwrap();
+        range.gate(&mut status, 766);
+        assert!(status.version.is_none());
+        range.gate(&mut status, 764);
+        assert_eq!(version(&status), ("Requires 1.20.3–1.21.1", 765));
+    }
+}